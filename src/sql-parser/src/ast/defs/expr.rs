@@ -23,6 +23,8 @@
 /// The parser does not distinguish between expressions of different types
 /// (e.g. boolean vs string), so the caller must handle expressions of
 /// inappropriate type, like `WHERE 1` or `SELECT 1=1`, as necessary.
+use std::fmt;
+
 use crate::ast::display::{self, AstDisplay, AstFormatter};
 use crate::ast::{
     BinaryOperator, DataType, Ident, ObjectName, OrderByExpr, Query, UnaryOperator, Value,
@@ -60,6 +62,33 @@ pub enum Expr {
         low: Box<Expr>,
         high: Box<Expr>,
     },
+    /// `<expr> [ NOT ] LIKE <pattern> [ ESCAPE <escape> ]`
+    Like {
+        negated: bool,
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+    },
+    /// `<expr> [ NOT ] ILIKE <pattern> [ ESCAPE <escape> ]`
+    ILike {
+        negated: bool,
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+    },
+    /// `<expr> [ NOT ] SIMILAR TO <pattern> [ ESCAPE <escape> ]`
+    SimilarTo {
+        negated: bool,
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+    },
+    /// `<expr> IS [ NOT ] DISTINCT FROM <expr>`
+    IsDistinctFrom {
+        negated: bool,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
     /// Binary operation e.g. `1 + 1` or `foo > bar`
     BinaryOp {
         left: Box<Expr>,
@@ -149,12 +178,12 @@ impl AstDisplay for Expr {
                 f.write_str(".*");
             }
             Expr::Parameter(n) => f.write_str(&format!("${}", n)),
-            Expr::IsNull(ast) => {
-                f.write_node(&ast);
+            Expr::IsNull(expr) => {
+                write_child(f, expr, PREC_IS, Assoc::None, Side::Left);
                 f.write_str(" IS NULL");
             }
-            Expr::IsNotNull(ast) => {
-                f.write_node(&ast);
+            Expr::IsNotNull(expr) => {
+                write_child(f, expr, PREC_IS, Assoc::None, Side::Left);
                 f.write_str(" IS NOT NULL");
             }
             Expr::InList {
@@ -162,7 +191,7 @@ impl AstDisplay for Expr {
                 list,
                 negated,
             } => {
-                f.write_node(&expr);
+                write_child(f, expr, PREC_BETWEEN, Assoc::None, Side::Left);
                 f.write_str(" ");
                 if *negated {
                     f.write_str("NOT ");
@@ -176,7 +205,7 @@ impl AstDisplay for Expr {
                 subquery,
                 negated,
             } => {
-                f.write_node(&expr);
+                write_child(f, expr, PREC_BETWEEN, Assoc::None, Side::Left);
                 f.write_str(" ");
                 if *negated {
                     f.write_str("NOT ");
@@ -191,57 +220,66 @@ impl AstDisplay for Expr {
                 low,
                 high,
             } => {
-                f.write_node(&expr);
+                write_child(f, expr, PREC_BETWEEN, Assoc::None, Side::Left);
                 if *negated {
                     f.write_str(" NOT");
                 }
                 f.write_str(" BETWEEN ");
-                f.write_node(&low);
+                write_child(f, low, PREC_BETWEEN, Assoc::None, Side::Right);
                 f.write_str(" AND ");
-                f.write_node(&high);
+                write_child(f, high, PREC_BETWEEN, Assoc::None, Side::Right);
+            }
+            Expr::Like {
+                negated,
+                expr,
+                pattern,
+                escape,
+            } => fmt_like(f, "LIKE", expr, *negated, pattern, escape),
+            Expr::ILike {
+                negated,
+                expr,
+                pattern,
+                escape,
+            } => fmt_like(f, "ILIKE", expr, *negated, pattern, escape),
+            Expr::SimilarTo {
+                negated,
+                expr,
+                pattern,
+                escape,
+            } => fmt_like(f, "SIMILAR TO", expr, *negated, pattern, escape),
+            Expr::IsDistinctFrom {
+                negated,
+                left,
+                right,
+            } => {
+                write_child(f, left, PREC_IS, Assoc::None, Side::Left);
+                f.write_str(" IS ");
+                if *negated {
+                    f.write_str("NOT ");
+                }
+                f.write_str("DISTINCT FROM ");
+                write_child(f, right, PREC_IS, Assoc::None, Side::Right);
             }
             Expr::BinaryOp { left, op, right } => {
-                f.write_node(&left);
+                let prec = binary_precedence(op);
+                let assoc = binary_assoc(op);
+                write_child(f, left, prec, assoc, Side::Left);
                 f.write_str(" ");
                 f.write_str(op);
                 f.write_str(" ");
-                f.write_node(&right);
+                write_child(f, right, prec, assoc, Side::Right);
             }
             Expr::UnaryOp { op, expr } => {
                 f.write_str(op);
                 f.write_str(" ");
-                f.write_node(&expr);
+                write_child(f, expr, unary_precedence(op), Assoc::Right, Side::Right);
             }
             Expr::Cast { expr, data_type } => {
-                // We are potentially rewriting an expression like
-                //     CAST(<expr> OP <expr> AS <type>)
-                // to
-                //     <expr> OP <expr>::<type>
-                // which could incorrectly change the meaning of the expression
-                // as the `::` binds tightly. To be safe, we wrap the inner
-                // expression in parentheses
-                //    (<expr> OP <expr>)::<type>
-                // unless the inner expression is of a type that we know is
-                // safe to follow with a `::` to without wrapping.
-                let needs_wrap = match **expr {
-                    Expr::Nested(_)
-                    | Expr::Value(_)
-                    | Expr::Cast { .. }
-                    | Expr::Function { .. }
-                    | Expr::Identifier { .. }
-                    | Expr::Extract { .. }
-                    | Expr::Trim { .. }
-                    | Expr::Collate { .. }
-                    | Expr::Coalesce { .. } => false,
-                    _ => true,
-                };
-                if needs_wrap {
-                    f.write_str('(');
-                }
-                f.write_node(&expr);
-                if needs_wrap {
-                    f.write_str(')');
-                }
+                // `::` binds tighter than nearly everything else, so wrap the
+                // inner expression in parentheses unless its own precedence is
+                // at least as high (e.g. re-serializing `(1 + 2)::int` must not
+                // turn into `1 + 2::int`, which means something different).
+                write_child(f, expr, PREC_CAST, Assoc::Left, Side::Left);
                 f.write_str("::");
                 f.write_node(data_type);
             }
@@ -328,7 +366,7 @@ impl AstDisplay for Expr {
                 right,
                 some,
             } => {
-                f.write_node(&left);
+                write_child(f, left, binary_precedence(op), binary_assoc(op), Side::Left);
                 f.write_str(" ");
                 f.write_str(op);
                 if *some {
@@ -341,7 +379,7 @@ impl AstDisplay for Expr {
                 f.write_str(")");
             }
             Expr::All { left, op, right } => {
-                f.write_node(&left);
+                write_child(f, left, binary_precedence(op), binary_assoc(op), Side::Left);
                 f.write_str(" ");
                 f.write_str(op);
                 f.write_str(" ALL (");
@@ -364,6 +402,145 @@ impl AstDisplay for Expr {
 }
 impl_display!(Expr);
 
+fn fmt_like(
+    f: &mut AstFormatter,
+    keyword: &str,
+    expr: &Expr,
+    negated: bool,
+    pattern: &Expr,
+    escape: &Option<Box<Expr>>,
+) {
+    write_child(f, expr, PREC_PATTERN, Assoc::None, Side::Left);
+    f.write_str(" ");
+    if negated {
+        f.write_str("NOT ");
+    }
+    f.write_str(keyword);
+    f.write_str(" ");
+    write_child(f, pattern, PREC_PATTERN, Assoc::None, Side::Right);
+    if let Some(escape) = escape {
+        f.write_str(" ESCAPE ");
+        f.write_node(&escape);
+    }
+}
+
+/// The associativity of a binary operator, used by [`write_child`] to decide
+/// which side of an equal-precedence parent needs parentheses.
+///
+/// Modeled after rustc's `AssocOp`/`Fixity`: rather than sprinkling `Nested`
+/// nodes or ad-hoc `needs_wrap` checks through the formatter, every operator
+/// is assigned a precedence and a fixity, and parentheses fall out of
+/// comparing a child's precedence against its parent's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Assoc {
+    Left,
+    Right,
+    /// Comparison-like operators, which don't associate at all (`a = b = c`
+    /// is not valid SQL), so either side gets wrapped at equal precedence.
+    None,
+}
+
+/// Which side of a binary operator a child expression is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+// Precedence levels, low to high, following the PostgreSQL operator
+// precedence table. Higher binds tighter.
+const PREC_OR: u8 = 1;
+const PREC_AND: u8 = 2;
+const PREC_NOT: u8 = 3;
+const PREC_IS: u8 = 4;
+const PREC_CMP: u8 = 5;
+/// `LIKE`/`ILIKE`/`SIMILAR TO` sit one level above comparisons.
+const PREC_PATTERN: u8 = 6;
+/// `BETWEEN`/`IN` get their own tier above pattern matching.
+const PREC_BETWEEN: u8 = 7;
+const PREC_OTHER: u8 = 8;
+const PREC_ADD: u8 = 9;
+const PREC_MUL: u8 = 10;
+const PREC_UNARY: u8 = 11;
+const PREC_CAST: u8 = 12;
+/// Atoms (identifiers, literals, function calls, already-parenthesized
+/// expressions, ...) never need to be wrapped, no matter the parent.
+const PREC_ATOM: u8 = u8::MAX;
+
+fn binary_precedence(op: &BinaryOperator) -> u8 {
+    match op.to_string().as_str() {
+        "OR" => PREC_OR,
+        "AND" => PREC_AND,
+        "=" | "<>" | "<" | ">" | "<=" | ">=" => PREC_CMP,
+        "LIKE" | "NOT LIKE" | "ILIKE" | "NOT ILIKE" => PREC_PATTERN,
+        "+" | "-" => PREC_ADD,
+        "*" | "/" | "%" => PREC_MUL,
+        _ => PREC_OTHER,
+    }
+}
+
+fn binary_assoc(op: &BinaryOperator) -> Assoc {
+    match op.to_string().as_str() {
+        "=" | "<>" | "<" | ">" | "<=" | ">=" => Assoc::None,
+        _ => Assoc::Left,
+    }
+}
+
+fn unary_precedence(op: &UnaryOperator) -> u8 {
+    match op.to_string().as_str() {
+        "NOT" => PREC_NOT,
+        _ => PREC_UNARY,
+    }
+}
+
+/// The precedence of `expr`'s top-level operator, as seen by a parent
+/// deciding whether `expr` needs to be wrapped in parentheses to print back
+/// with the same meaning.
+fn expr_precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::BinaryOp { op, .. } => binary_precedence(op),
+        Expr::UnaryOp { op, .. } => unary_precedence(op),
+        Expr::Cast { .. } => PREC_CAST,
+        Expr::IsNull(_) | Expr::IsNotNull(_) | Expr::IsDistinctFrom { .. } => PREC_IS,
+        Expr::Like { .. } | Expr::ILike { .. } | Expr::SimilarTo { .. } => PREC_PATTERN,
+        Expr::Between { .. } | Expr::InList { .. } | Expr::InSubquery { .. } => PREC_BETWEEN,
+        Expr::Any { op, .. } | Expr::All { op, .. } => binary_precedence(op),
+        _ => PREC_ATOM,
+    }
+}
+
+fn needs_parens(expr: &Expr, parent_prec: u8, parent_assoc: Assoc, side: Side) -> bool {
+    let child_prec = expr_precedence(expr);
+    match child_prec.cmp(&parent_prec) {
+        std::cmp::Ordering::Less => true,
+        std::cmp::Ordering::Greater => false,
+        std::cmp::Ordering::Equal => match parent_assoc {
+            Assoc::Left => side == Side::Right,
+            Assoc::Right => side == Side::Left,
+            Assoc::None => true,
+        },
+    }
+}
+
+/// Writes `expr` as the child of a parent operator with precedence
+/// `parent_prec` and fixity `parent_assoc`, wrapping it in parentheses only
+/// when necessary to preserve the parent's structure.
+fn write_child(
+    f: &mut AstFormatter,
+    expr: &Expr,
+    parent_prec: u8,
+    parent_assoc: Assoc,
+    side: Side,
+) {
+    if needs_parens(expr, parent_prec, parent_assoc, side) {
+        f.write_str("(");
+        f.write_node(expr);
+        f.write_str(")");
+    } else {
+        f.write_node(expr);
+    }
+}
+
 impl Expr {
     pub fn is_string_literal(&self) -> bool {
         if let Expr::Value(Value::String(_)) = self {
@@ -372,11 +549,244 @@ impl Expr {
             false
         }
     }
+
+    /// Invokes `f` on this expression and, recursively, on every expression
+    /// nested within it (function arguments, `CASE` branches, window
+    /// partition/order expressions, etc.), in preorder. Subqueries are not
+    /// descended into: the queries inside `EXISTS`, `IN (SELECT ...)`,
+    /// `ANY`/`ALL` and scalar subqueries are skipped.
+    ///
+    /// This saves callers from hand-writing a recursive match over every
+    /// `Expr` variant each time they need to answer a question like "does
+    /// this expression contain a subquery?" -- see [`Expr::contains_subquery`],
+    /// [`Expr::contains_window_spec`], and [`Expr::contains_aggregate`].
+    pub fn visit<'a, F>(&'a self, f: &mut F)
+    where
+        F: FnMut(&'a Expr),
+    {
+        f(self);
+        self.visit_children(f);
+    }
+
+    fn visit_children<'a, F>(&'a self, f: &mut F)
+    where
+        F: FnMut(&'a Expr),
+    {
+        match self {
+            Expr::Identifier(_)
+            | Expr::QualifiedWildcard(_)
+            | Expr::Parameter(_)
+            | Expr::Value(_)
+            | Expr::Exists(_)
+            | Expr::Subquery(_) => (),
+            Expr::IsNull(expr) | Expr::IsNotNull(expr) => expr.visit(f),
+            Expr::InList { expr, list, .. } => {
+                expr.visit(f);
+                for e in list {
+                    e.visit(f);
+                }
+            }
+            Expr::InSubquery { expr, .. } => expr.visit(f),
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                expr.visit(f);
+                low.visit(f);
+                high.visit(f);
+            }
+            Expr::Like {
+                expr,
+                pattern,
+                escape,
+                ..
+            }
+            | Expr::ILike {
+                expr,
+                pattern,
+                escape,
+                ..
+            }
+            | Expr::SimilarTo {
+                expr,
+                pattern,
+                escape,
+                ..
+            } => {
+                expr.visit(f);
+                pattern.visit(f);
+                if let Some(escape) = escape {
+                    escape.visit(f);
+                }
+            }
+            Expr::IsDistinctFrom { left, right, .. } => {
+                left.visit(f);
+                right.visit(f);
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                left.visit(f);
+                right.visit(f);
+            }
+            Expr::UnaryOp { expr, .. } => expr.visit(f),
+            Expr::Cast { expr, .. } => expr.visit(f),
+            Expr::Extract { expr, .. } => expr.visit(f),
+            Expr::Trim { exprs, .. } => {
+                for e in exprs {
+                    e.visit(f);
+                }
+            }
+            Expr::Collate { expr, .. } => expr.visit(f),
+            Expr::Coalesce { exprs } => {
+                for e in exprs {
+                    e.visit(f);
+                }
+            }
+            Expr::Nested(expr) => expr.visit(f),
+            Expr::Row { exprs } => {
+                for e in exprs {
+                    e.visit(f);
+                }
+            }
+            Expr::Function(fun) => {
+                if let FunctionArgs::Args(args) = &fun.args {
+                    for e in args {
+                        e.visit(f);
+                    }
+                }
+                if let Some(filter) = &fun.filter {
+                    filter.visit(f);
+                }
+                if let Some(over) = &fun.over {
+                    for e in &over.partition_by {
+                        e.visit(f);
+                    }
+                    for order_by_expr in &over.order_by {
+                        order_by_expr.expr.visit(f);
+                    }
+                    if let Some(window_frame) = &over.window_frame {
+                        visit_window_frame_bound(&window_frame.start_bound, f);
+                        if let Some(end_bound) = &window_frame.end_bound {
+                            visit_window_frame_bound(end_bound, f);
+                        }
+                    }
+                }
+            }
+            Expr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                if let Some(operand) = operand {
+                    operand.visit(f);
+                }
+                for e in conditions {
+                    e.visit(f);
+                }
+                for e in results {
+                    e.visit(f);
+                }
+                if let Some(else_result) = else_result {
+                    else_result.visit(f);
+                }
+            }
+            Expr::Any { left, .. } => left.visit(f),
+            Expr::All { left, .. } => left.visit(f),
+            Expr::List(exprs) => {
+                for e in exprs {
+                    e.visit(f);
+                }
+            }
+        }
+    }
+
+    /// Does this expression contain a subquery, e.g. `EXISTS (...)`,
+    /// `(SELECT ...)`, `x IN (SELECT ...)`, or `x > ANY (SELECT ...)`?
+    pub fn contains_subquery(&self) -> bool {
+        let mut found = false;
+        self.visit(&mut |e| {
+            found |= matches!(
+                e,
+                Expr::Exists(_)
+                    | Expr::Subquery(_)
+                    | Expr::InSubquery { .. }
+                    | Expr::Any { .. }
+                    | Expr::All { .. }
+            );
+        });
+        found
+    }
+
+    /// Does this expression contain a window function call, e.g.
+    /// `row_number() OVER (...)`?
+    pub fn contains_window_spec(&self) -> bool {
+        let mut found = false;
+        self.visit(&mut |e| {
+            if let Expr::Function(fun) = e {
+                found |= fun.over.is_some();
+            }
+        });
+        found
+    }
+
+    /// Does this expression contain a call to a function for which `is_agg`
+    /// returns `true`?
+    pub fn contains_aggregate(&self, is_agg: impl Fn(&ObjectName) -> bool) -> bool {
+        let mut found = false;
+        self.visit(&mut |e| {
+            if let Expr::Function(fun) = e {
+                found |= is_agg(&fun.name);
+            }
+        });
+        found
+    }
+}
+
+/// Visits the offset expression (if any) carried by a frame bound,
+/// as part of [`Expr::visit_children`]'s walk through a window function's
+/// `OVER (...)` clause.
+fn visit_window_frame_bound<'a, F>(bound: &'a WindowFrameBound, f: &mut F)
+where
+    F: FnMut(&'a Expr),
+{
+    match bound {
+        WindowFrameBound::CurrentRow => (),
+        WindowFrameBound::Preceding(expr) | WindowFrameBound::Following(expr) => {
+            if let Some(expr) = expr {
+                expr.visit(f);
+            }
+        }
+    }
+}
+
+/// A single `name AS (...)` entry of a query's `WINDOW` clause, e.g. the
+/// `w AS (PARTITION BY x)` in `SELECT ... WINDOW w AS (PARTITION BY x)`.
+///
+/// This pairs with [`WindowSpec::existing_window_name`], which lets an
+/// `OVER (...)` clause refer back to a name defined this way. The query
+/// level (`Select`) is expected to carry `window_clause: Vec<WindowDefinition>`
+/// so these definitions are actually reachable from a query; that field
+/// lives in the query AST defs alongside `Select`, outside this module.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WindowDefinition(pub Ident, pub WindowSpec);
+
+impl AstDisplay for WindowDefinition {
+    fn fmt(&self, f: &mut AstFormatter) {
+        f.write_node(&self.0);
+        f.write_str(" AS (");
+        f.write_node(&self.1);
+        f.write_str(")");
+    }
 }
+impl_display!(WindowDefinition);
 
-/// A window specification (i.e. `OVER (PARTITION BY .. ORDER BY .. etc.)`)
+/// A window specification (i.e. `OVER (PARTITION BY .. ORDER BY .. etc.)`),
+/// optionally layered on top of a named window from a `WINDOW` clause (see
+/// [`WindowDefinition`]), e.g. `OVER (w ORDER BY ...)`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct WindowSpec {
+    /// The name of a window defined in the query's `WINDOW` clause that this
+    /// spec refines, e.g. the `w` in `OVER (w ORDER BY foo)`.
+    pub existing_window_name: Option<Ident>,
     pub partition_by: Vec<Expr>,
     pub order_by: Vec<OrderByExpr>,
     pub window_frame: Option<WindowFrame>,
@@ -385,7 +795,12 @@ pub struct WindowSpec {
 impl AstDisplay for WindowSpec {
     fn fmt(&self, f: &mut AstFormatter) {
         let mut delim = "";
+        if let Some(existing_window_name) = &self.existing_window_name {
+            delim = " ";
+            f.write_node(existing_window_name);
+        }
         if !self.partition_by.is_empty() {
+            f.write_str(delim);
             delim = " ";
             f.write_str("PARTITION BY ");
             f.write_node(&display::comma_separated(&self.partition_by));
@@ -410,6 +825,10 @@ impl AstDisplay for WindowSpec {
                 f.write_str(" ");
                 f.write_node(&window_frame.start_bound);
             }
+            if let Some(exclude) = &window_frame.exclude {
+                f.write_str(" EXCLUDE ");
+                f.write_node(exclude);
+            }
         }
     }
 }
@@ -419,7 +838,8 @@ impl_display!(WindowSpec);
 /// `RANGE UNBOUNDED PRECEDING` or `ROWS BETWEEN 5 PRECEDING AND CURRENT ROW`.
 ///
 /// Note: The parser does not validate the specified bounds; the caller should
-/// reject invalid bounds like `ROWS UNBOUNDED FOLLOWING` before execution.
+/// call [`WindowFrame::validate`] to reject invalid bounds like
+/// `ROWS UNBOUNDED FOLLOWING` before execution.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct WindowFrame {
     pub units: WindowFrameUnits,
@@ -428,9 +848,106 @@ pub struct WindowFrame {
     /// indicates the shorthand form (e.g. `ROWS 1 PRECEDING`), which must
     /// behave the same as `end_bound = WindowFrameBound::CurrentRow`.
     pub end_bound: Option<WindowFrameBound>,
-    // TBD: EXCLUDE
+    pub exclude: Option<WindowFrameExclusion>,
 }
 
+impl WindowFrame {
+    /// Checks that this frame's bounds are legal, per the rules Postgres
+    /// applies when validating a frame clause:
+    ///
+    /// * the start bound may not be `UNBOUNDED FOLLOWING`
+    /// * the end bound may not be `UNBOUNDED PRECEDING`
+    /// * the start bound may not come after the end bound, ordering
+    ///   `UNBOUNDED PRECEDING < <expr> PRECEDING < CURRENT ROW
+    ///   < <expr> FOLLOWING < UNBOUNDED FOLLOWING`, where the shorthand
+    ///   single-bound form is treated as ending at `CURRENT ROW`
+    pub fn validate(&self) -> Result<(), WindowFrameError> {
+        if let WindowFrameBound::Following(None) = self.start_bound {
+            return Err(WindowFrameError::StartBoundUnboundedFollowing);
+        }
+        let end_bound = self
+            .end_bound
+            .as_ref()
+            .unwrap_or(&WindowFrameBound::CurrentRow);
+        if let WindowFrameBound::Preceding(None) = end_bound {
+            return Err(WindowFrameError::EndBoundUnboundedPreceding);
+        }
+        if bound_rank(&self.start_bound) > bound_rank(end_bound) {
+            return Err(WindowFrameError::StartBoundAfterEndBound);
+        }
+        Ok(())
+    }
+}
+
+/// The relative ordering of a [`WindowFrameBound`], from the start of a frame
+/// to its end: `UNBOUNDED PRECEDING < <expr> PRECEDING < CURRENT ROW
+/// < <expr> FOLLOWING < UNBOUNDED FOLLOWING`.
+fn bound_rank(bound: &WindowFrameBound) -> u8 {
+    match bound {
+        WindowFrameBound::Preceding(None) => 0,
+        WindowFrameBound::Preceding(Some(_)) => 1,
+        WindowFrameBound::CurrentRow => 2,
+        WindowFrameBound::Following(Some(_)) => 3,
+        WindowFrameBound::Following(None) => 4,
+    }
+}
+
+/// An invalid [`WindowFrame`], as rejected by [`WindowFrame::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowFrameError {
+    /// The start bound was `UNBOUNDED FOLLOWING`.
+    StartBoundUnboundedFollowing,
+    /// The end bound was `UNBOUNDED PRECEDING`.
+    EndBoundUnboundedPreceding,
+    /// The start bound came after the end bound, e.g.
+    /// `BETWEEN CURRENT ROW AND 1 PRECEDING`.
+    StartBoundAfterEndBound,
+}
+
+impl fmt::Display for WindowFrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowFrameError::StartBoundUnboundedFollowing => {
+                write!(f, "frame start cannot be UNBOUNDED FOLLOWING")
+            }
+            WindowFrameError::EndBoundUnboundedPreceding => {
+                write!(f, "frame end cannot be UNBOUNDED PRECEDING")
+            }
+            WindowFrameError::StartBoundAfterEndBound => {
+                write!(f, "frame start cannot be greater than frame end")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WindowFrameError {}
+
+/// Specifies [WindowFrame]'s `EXCLUDE` clause, which narrows the window frame
+/// to exclude rows around the current row.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WindowFrameExclusion {
+    /// `EXCLUDE CURRENT ROW`
+    CurrentRow,
+    /// `EXCLUDE GROUP`
+    Group,
+    /// `EXCLUDE TIES`
+    Ties,
+    /// `EXCLUDE NO OTHERS`
+    NoOthers,
+}
+
+impl AstDisplay for WindowFrameExclusion {
+    fn fmt(&self, f: &mut AstFormatter) {
+        f.write_str(match self {
+            WindowFrameExclusion::CurrentRow => "CURRENT ROW",
+            WindowFrameExclusion::Group => "GROUP",
+            WindowFrameExclusion::Ties => "TIES",
+            WindowFrameExclusion::NoOthers => "NO OTHERS",
+        })
+    }
+}
+impl_display!(WindowFrameExclusion);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum WindowFrameUnits {
     Rows,
@@ -449,15 +966,19 @@ impl AstDisplay for WindowFrameUnits {
 }
 impl_display!(WindowFrameUnits);
 
-/// Specifies [WindowFrame]'s `start_bound` and `end_bound`
+/// Specifies [WindowFrame]'s `start_bound` and `end_bound`.
+///
+/// `ROWS` frames carry an integer literal offset, while `RANGE` and `GROUPS`
+/// frames may carry any expression (e.g. `INTERVAL '1 day'`); `None` always
+/// means `UNBOUNDED`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum WindowFrameBound {
     /// `CURRENT ROW`
     CurrentRow,
-    /// `<N> PRECEDING` or `UNBOUNDED PRECEDING`
-    Preceding(Option<u64>),
-    /// `<N> FOLLOWING` or `UNBOUNDED FOLLOWING`.
-    Following(Option<u64>),
+    /// `<expr> PRECEDING` or `UNBOUNDED PRECEDING`
+    Preceding(Option<Box<Expr>>),
+    /// `<expr> FOLLOWING` or `UNBOUNDED FOLLOWING`.
+    Following(Option<Box<Expr>>),
 }
 
 impl AstDisplay for WindowFrameBound {
@@ -466,12 +987,12 @@ impl AstDisplay for WindowFrameBound {
             WindowFrameBound::CurrentRow => f.write_str("CURRENT ROW"),
             WindowFrameBound::Preceding(None) => f.write_str("UNBOUNDED PRECEDING"),
             WindowFrameBound::Following(None) => f.write_str("UNBOUNDED FOLLOWING"),
-            WindowFrameBound::Preceding(Some(n)) => {
-                f.write_str(n);
+            WindowFrameBound::Preceding(Some(e)) => {
+                f.write_node(e);
                 f.write_str(" PRECEDING");
             }
-            WindowFrameBound::Following(Some(n)) => {
-                f.write_str(n);
+            WindowFrameBound::Following(Some(e)) => {
+                f.write_node(e);
                 f.write_str(" FOLLOWING");
             }
         }
@@ -555,3 +1076,206 @@ impl AstDisplay for TrimSide {
     }
 }
 impl_display!(TrimSide);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str) -> Box<Expr> {
+        Box::new(Expr::Identifier(vec![Ident::new(name)]))
+    }
+
+    fn binary_op(left: Box<Expr>, op: BinaryOperator, right: Box<Expr>) -> Box<Expr> {
+        Box::new(Expr::BinaryOp { left, op, right })
+    }
+
+    #[test]
+    fn binary_op_display_omits_redundant_parens() {
+        // `a + b + c` is left-associative, so re-serializing the left-nested
+        // tree should not add parentheses around the left child.
+        let expr = binary_op(
+            binary_op(ident("a"), BinaryOperator::Plus, ident("b")),
+            BinaryOperator::Plus,
+            ident("c"),
+        );
+        assert_eq!(expr.to_string(), "a + b + c");
+    }
+
+    #[test]
+    fn binary_op_display_wraps_equal_precedence_right_child() {
+        // `a - (b - c)` is not the same as `a - b - c`, so the right child
+        // must stay parenthesized.
+        let expr = binary_op(
+            ident("a"),
+            BinaryOperator::Minus,
+            binary_op(ident("b"), BinaryOperator::Minus, ident("c")),
+        );
+        assert_eq!(expr.to_string(), "a - (b - c)");
+    }
+
+    #[test]
+    fn binary_op_display_wraps_looser_operator() {
+        // `*` binds tighter than `+`, so `(a + b) * c` must keep its parens.
+        let expr = binary_op(
+            binary_op(ident("a"), BinaryOperator::Plus, ident("b")),
+            BinaryOperator::Multiply,
+            ident("c"),
+        );
+        assert_eq!(expr.to_string(), "(a + b) * c");
+    }
+
+    #[test]
+    fn is_null_wraps_looser_child_but_not_atoms() {
+        // `OR` (1) binds looser than `IS` (4), so the child needs parens;
+        // `+` (9) binds tighter, so it must print without them.
+        let or = Expr::IsNull(binary_op(ident("a"), BinaryOperator::Or, ident("b")));
+        assert_eq!(or.to_string(), "(a OR b) IS NULL");
+
+        let add = Expr::IsNull(binary_op(ident("a"), BinaryOperator::Plus, ident("b")));
+        assert_eq!(add.to_string(), "a + b IS NULL");
+
+        let atom = Expr::IsNull(ident("a"));
+        assert_eq!(atom.to_string(), "a IS NULL");
+    }
+
+    #[test]
+    fn between_wraps_looser_children() {
+        let expr = Expr::Between {
+            expr: binary_op(ident("a"), BinaryOperator::Or, ident("b")),
+            negated: false,
+            low: Box::new(Expr::Value(Value::Number("1".into()))),
+            high: Box::new(Expr::Value(Value::Number("2".into()))),
+        };
+        assert_eq!(expr.to_string(), "(a OR b) BETWEEN 1 AND 2");
+    }
+
+    #[test]
+    fn window_spec_display_separates_name_and_partition_by() {
+        let spec = WindowSpec {
+            existing_window_name: Some(Ident::new("w")),
+            partition_by: vec![*ident("x")],
+            order_by: vec![],
+            window_frame: None,
+        };
+        assert_eq!(spec.to_string(), "w PARTITION BY x");
+    }
+
+    #[test]
+    fn window_frame_validate_rejects_unbounded_following_start() {
+        let frame = WindowFrame {
+            units: WindowFrameUnits::Rows,
+            start_bound: WindowFrameBound::Following(None),
+            end_bound: None,
+            exclude: None,
+        };
+        assert_eq!(
+            frame.validate(),
+            Err(WindowFrameError::StartBoundUnboundedFollowing)
+        );
+    }
+
+    #[test]
+    fn window_frame_validate_rejects_unbounded_preceding_end() {
+        let frame = WindowFrame {
+            units: WindowFrameUnits::Rows,
+            start_bound: WindowFrameBound::CurrentRow,
+            end_bound: Some(WindowFrameBound::Preceding(None)),
+            exclude: None,
+        };
+        assert_eq!(
+            frame.validate(),
+            Err(WindowFrameError::EndBoundUnboundedPreceding)
+        );
+    }
+
+    #[test]
+    fn window_frame_validate_rejects_start_after_end() {
+        // Neither bound is the unbounded edge case rejected by the earlier
+        // checks, so this only fails because `Following` (rank 3) outranks
+        // `Preceding` (rank 1): `BETWEEN 1 FOLLOWING AND 1 PRECEDING`.
+        let frame = WindowFrame {
+            units: WindowFrameUnits::Rows,
+            start_bound: WindowFrameBound::Following(Some(Box::new(Expr::Value(Value::Number(
+                "1".into(),
+            ))))),
+            end_bound: Some(WindowFrameBound::Preceding(Some(Box::new(Expr::Value(
+                Value::Number("1".into()),
+            ))))),
+            exclude: None,
+        };
+        assert_eq!(
+            frame.validate(),
+            Err(WindowFrameError::StartBoundAfterEndBound)
+        );
+
+        let frame = WindowFrame {
+            units: WindowFrameUnits::Rows,
+            start_bound: WindowFrameBound::CurrentRow,
+            end_bound: Some(WindowFrameBound::Following(Some(Box::new(Expr::Value(
+                Value::Number("1".into()),
+            ))))),
+            exclude: None,
+        };
+        assert!(frame.validate().is_ok());
+    }
+
+    #[test]
+    fn window_frame_validate_accepts_shorthand_as_ending_at_current_row() {
+        let frame = WindowFrame {
+            units: WindowFrameUnits::Rows,
+            start_bound: WindowFrameBound::Preceding(Some(Box::new(Expr::Value(Value::Number(
+                "1".into(),
+            ))))),
+            end_bound: None,
+            exclude: None,
+        };
+        assert!(frame.validate().is_ok());
+    }
+
+    #[test]
+    fn contains_subquery_is_false_with_no_subquery_present() {
+        // `Query` isn't constructible from this module, so this only
+        // exercises the negative path; the positive path (an `Exists`,
+        // `Subquery`, `InSubquery`, `Any`, or `All` node anywhere in the
+        // tree) is not exercised by this test.
+        let expr = binary_op(ident("a"), BinaryOperator::Plus, ident("b"));
+        assert!(!expr.contains_subquery());
+    }
+
+    #[test]
+    fn contains_window_spec_finds_nested_over_clause() {
+        let window_fn = Expr::Function(Function {
+            name: ObjectName(vec![Ident::new("row_number")]),
+            args: FunctionArgs::Args(vec![]),
+            filter: None,
+            over: Some(WindowSpec {
+                existing_window_name: None,
+                partition_by: vec![],
+                order_by: vec![],
+                window_frame: None,
+            }),
+            distinct: false,
+        });
+        let expr = Expr::BinaryOp {
+            left: Box::new(window_fn),
+            op: BinaryOperator::Plus,
+            right: ident("a"),
+        };
+        assert!(expr.contains_window_spec());
+        assert!(!ident("a").contains_window_spec());
+    }
+
+    #[test]
+    fn contains_aggregate_uses_caller_supplied_predicate() {
+        let call = Expr::Function(Function {
+            name: ObjectName(vec![Ident::new("sum")]),
+            args: FunctionArgs::Args(vec![*ident("x")]),
+            filter: None,
+            over: None,
+            distinct: false,
+        });
+        let is_agg = |name: &ObjectName| name.to_string() == "sum";
+        assert!(call.contains_aggregate(is_agg));
+        assert!(!ident("a").contains_aggregate(is_agg));
+    }
+}